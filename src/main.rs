@@ -1,16 +1,25 @@
 use std::fs::File;
 use std::io::{Read, BufReader};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use clap::{App, Arg, ArgMatches};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use indicatif::{ProgressBar, ProgressStyle};
+use rayon::prelude::*;
 use serde_json::Value;
 use jsonschema_valid::Config as SchemaConfig;
 
 fn main() {
     let matches = app().get_matches();
-    match execute(&matches) {
-        Ok(_) => return,
-        Err(e) => println!("{}", e),
+    let result = match matches.subcommand() {
+        ("schema", Some(sub_matches)) => execute_schema(sub_matches),
+        _ => execute(&matches),
+    };
+    match result {
+        Ok(exit_code) => std::process::exit(exit_code),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
     }
 }
 
@@ -24,11 +33,49 @@ fn app<'a, 'b>() -> App<'a, 'b> {
             .default_value("./schema.json"))
         .arg(Arg::with_name("csv-file")
             .index(1)
-            .required(true))
+            .required(true)
+            .multiple(true)
+            .help("One or more CSV files to validate"))
+        .arg(Arg::with_name("format")
+            .long("--format")
+            .takes_value(true)
+            .possible_values(&["human", "json"])
+            .default_value("human")
+            .help("Output format for validation results"))
+        .arg(Arg::with_name("jobs")
+            .long("--jobs")
+            .takes_value(true)
+            .help("Number of worker threads to validate with (default: number of CPUs; 1 = sequential)"))
+        .arg(Arg::with_name("array-delimiter")
+            .long("--array-delimiter")
+            .takes_value(true)
+            .default_value(";")
+            .help("Separator used to split a single cell into a JSON array, for properties whose schema type is \"array\""))
+        .arg(Arg::with_name("draft")
+            .long("--draft")
+            .takes_value(true)
+            .possible_values(&["4", "6", "7", "2019-09", "2020-12"])
+            .default_value("7")
+            .help("JSON Schema draft to validate against"))
+        .arg(Arg::with_name("fail-fast")
+            .long("--fail-fast")
+            .takes_value(false)
+            .help("Stop at the first failing record, skipping any remaining files"))
+        .subcommand(SubCommand::with_name("schema")
+            .about("Infers a JSON Schema from a reference CSV file")
+            .arg(Arg::with_name("csv-file")
+                .index(1)
+                .required(true))
+            .arg(Arg::with_name("sample-size")
+                .long("--sample-size")
+                .takes_value(true)
+                .help("Only scan the first N rows when inferring the schema (default: scan every row)")))
 }
 
-/// Executes according to the given command-line arguments
-fn execute(args: &ArgMatches) -> Result<(), String> {
+/// Executes according to the given command-line arguments, validating every
+/// given `csv-file` against the same schema and returning the combined
+/// process exit code (`0` only if every file validated cleanly).
+fn execute(args: &ArgMatches) -> Result<i32, String> {
 
     // The schema_path comes from the --schema command-line argument
     let schema_path = args.value_of("schema")
@@ -36,39 +83,353 @@ fn execute(args: &ArgMatches) -> Result<(), String> {
         // argument has a default value, so this is impossible to fail.
         .expect("schema argument is required");
 
-    let csv_path = args.value_of("csv-file")
+    let csv_paths: Vec<&str> = args.values_of("csv-file")
         // Use the unsafe "expect" here because the "input"
         // argument is marked as required, so this is impossible to fail.
-        .expect("csv-file argument is required");
+        .expect("csv-file argument is required")
+        .collect();
 
     // Try to open the schema file using the schema path
     let schema_file = File::open(schema_path)
         .map_err(|e| format!("failed to open schema file ({}): {:?}", schema_path, e))?;
 
-    // Try to open the csv file using the csv path
-    let csv_file = File::open(csv_path)
-        .map_err(|e| format!("failed to open csv file ({}): {:?}", csv_path, e))?;
-
     // The schema file is itself JSON, so parse it into a JSON representation
     let json_schema: Value = serde_json::from_reader(schema_file)
         .map_err(|e| format!("failed to parse schema as JSON: {:?}", e))?;
 
+    let draft = parse_draft(args.value_of("draft").unwrap_or("7"))?;
+
     // Produce a SchemaConfig from the JSON schema object.
-    let schema_config = SchemaConfig::from_schema(&json_schema, None).unwrap();
+    let schema_config = SchemaConfig::from_schema(&json_schema, Some(draft.clone())).unwrap();
 
     // Use the SchemaConfig for validating values in CSV.
     let validator = CsvValidator::new(schema_config);
 
-    let result = validator.validate(BufReader::new(csv_file));
+    let jobs = args.value_of("jobs")
+        .map(|s| s.parse::<usize>().map_err(|e| format!("invalid --jobs ({}): {:?}", s, e)))
+        .transpose()?
+        .unwrap_or_else(num_cpus::get);
 
-    match result {
-        Ok(num) => println!("Successfully validated {} records", num),
-        Err(num) => println!("Validation failed with {} errors", num),
+    let array_delimiter_arg = args.value_of("array-delimiter").unwrap_or(";");
+    let array_delimiter = array_delimiter_arg.chars().next()
+        .filter(|_| array_delimiter_arg.chars().count() == 1)
+        .ok_or_else(|| format!("--array-delimiter must be a single character, got \"{}\"", array_delimiter_arg))?;
+
+    let fail_fast = args.is_present("fail-fast");
+    let output_format = args.value_of("format").unwrap_or("human");
+
+    let mut any_errors = false;
+    for csv_path in csv_paths {
+        let csv_file = File::open(csv_path)
+            .map_err(|e| format!("failed to open csv file ({}): {:?}", csv_path, e))?;
+
+        let report = validator.validate(BufReader::new(csv_file), jobs, array_delimiter, fail_fast, Some(draft.clone()));
+
+        match output_format {
+            "json" => print_json_report(csv_path, &report),
+            _ => print_human_report(csv_path, &report),
+        }
+
+        if !report.errors.is_empty() {
+            any_errors = true;
+            if fail_fast {
+                break;
+            }
+        }
+    }
+
+    if any_errors { Ok(1) } else { Ok(0) }
+}
+
+/// Maps a `--draft` CLI value to the corresponding JSON Schema draft.
+fn parse_draft(name: &str) -> Result<jsonschema_valid::schemas::Draft, String> {
+    use jsonschema_valid::schemas::Draft;
+    match name {
+        "4" => Ok(Draft::Draft4),
+        "6" => Ok(Draft::Draft6),
+        "7" => Ok(Draft::Draft7),
+        "2019-09" => Ok(Draft::Draft201909),
+        "2020-12" => Ok(Draft::Draft202012),
+        other => Err(format!("unsupported --draft value: {}", other)),
+    }
+}
+
+/// Executes the `schema` subcommand, inferring a JSON Schema from a CSV file
+/// and printing it to stdout.
+fn execute_schema(args: &ArgMatches) -> Result<i32, String> {
+    let csv_path = args.value_of("csv-file")
+        .expect("csv-file argument is required");
+
+    let sample_size = args.value_of("sample-size")
+        .map(|s| s.parse::<usize>()
+            .map_err(|e| format!("invalid --sample-size ({}): {:?}", s, e)))
+        .transpose()?;
+
+    let csv_file = File::open(csv_path)
+        .map_err(|e| format!("failed to open csv file ({}): {:?}", csv_path, e))?;
+
+    let schema = infer_schema(BufReader::new(csv_file), sample_size)?;
+
+    let schema_json = serde_json::to_string_pretty(&schema)
+        .map_err(|e| format!("failed to serialize inferred schema: {:?}", e))?;
+    println!("{}", schema_json);
+
+    Ok(0)
+}
+
+/// Prints a `ValidationReport` as human-readable text: each failing record's
+/// errors (prefixed with their field path) to stderr, then a one-line
+/// summary for the file to stdout.
+fn print_human_report(csv_path: &str, report: &ValidationReport) {
+    for record_error in &report.errors {
+        eprintln!("Validation error in {} on record {}:", csv_path, record_error.record);
+        for field_error in &record_error.errors {
+            eprintln!("{} {}", field_error.path, field_error.message);
+        }
     }
 
-    Ok(())
+    if report.errors.is_empty() {
+        println!("{}: successfully validated {} records", csv_path, report.success_count);
+    } else {
+        println!("{}: validation failed with {} errors", csv_path, report.errors.len());
+    }
+}
+
+/// Prints a `ValidationReport` as one JSON object per failing field to
+/// stdout, suitable for piping into other tools.
+fn print_json_report(csv_path: &str, report: &ValidationReport) {
+    for record_error in &report.errors {
+        for field_error in &record_error.errors {
+            let line = serde_json::json!({
+                "file": csv_path,
+                "record": record_error.record,
+                "path": field_error.path,
+                "message": field_error.message,
+            });
+            println!("{}", line);
+        }
+    }
 }
 
+/// The narrowest JSON type that fits every non-empty value observed in a
+/// column so far, ordered from most to least restrictive.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum InferredType {
+    Integer,
+    Number,
+    Boolean,
+    String,
+}
+
+/// Widens two observed types to the narrowest type that still fits both.
+fn widen(a: InferredType, b: InferredType) -> InferredType {
+    use InferredType::*;
+    match (a, b) {
+        (x, y) if x == y => x,
+        (Integer, Number) | (Number, Integer) => Number,
+        _ => String,
+    }
+}
+
+/// The maximum number of distinct values a column may have before it is
+/// considered too unique to be usefully expressed as an `enum`.
+const MAX_ENUM_DISTINCT: usize = 10;
+
+/// Accumulates the observations needed to infer a JSON Schema property for a
+/// single CSV column.
+struct ColumnStats {
+    /// `None` until the first non-empty value is observed.
+    inferred_type: Option<InferredType>,
+    values_seen: usize,
+    empty_seen: usize,
+    distinct: HashSet<String>,
+    min: Option<f64>,
+    max: Option<f64>,
+}
+
+impl ColumnStats {
+    fn new() -> ColumnStats {
+        ColumnStats {
+            inferred_type: None,
+            values_seen: 0,
+            empty_seen: 0,
+            distinct: HashSet::new(),
+            min: None,
+            max: None,
+        }
+    }
+
+    /// Folds one cell's raw text into this column's running type inference.
+    fn observe(&mut self, value: &str) {
+        if value.is_empty() {
+            self.empty_seen += 1;
+            return;
+        }
+
+        self.values_seen += 1;
+        self.distinct.insert(value.to_string());
+
+        let value_type = if value.parse::<i64>().is_ok() {
+            InferredType::Integer
+        } else if value.parse::<f64>().is_ok() {
+            InferredType::Number
+        } else if value.eq_ignore_ascii_case("true") || value.eq_ignore_ascii_case("false") {
+            InferredType::Boolean
+        } else {
+            InferredType::String
+        };
+        self.inferred_type = Some(match self.inferred_type {
+            Some(existing) => widen(existing, value_type),
+            None => value_type,
+        });
+
+        if let Ok(number) = value.parse::<f64>() {
+            self.min = Some(self.min.map_or(number, |m| m.min(number)));
+            self.max = Some(self.max.map_or(number, |m| m.max(number)));
+        }
+    }
+}
+
+/// Builds a JSON number `Value` that prints as an integer when `n` has no
+/// fractional part, and as a float otherwise.
+fn json_number(n: f64) -> Value {
+    if n.fract() == 0.0 {
+        Value::from(n as i64)
+    } else {
+        Value::from(n)
+    }
+}
+
+/// Builds the `properties` entry for a single column from its accumulated
+/// stats, including an `enum` when the column has few distinct values and
+/// `minimum`/`maximum` when the column is numeric. Columns with empty cells
+/// allow `"null"` alongside their inferred type (and in their `enum`, if
+/// any), since `coerce_field` maps an empty cell to `Value::Null` whenever
+/// `"null"` is a declared type; without it, a schema inferred from a file
+/// would reject that very file's own empty cells.
+fn column_schema(stats: &ColumnStats) -> Value {
+    let type_name = match stats.inferred_type {
+        None => "string",
+        Some(InferredType::Integer) => "integer",
+        Some(InferredType::Number) => "number",
+        Some(InferredType::Boolean) => "boolean",
+        Some(InferredType::String) => "string",
+    };
+    let has_nulls = stats.empty_seen > 0;
+
+    let mut property = serde_json::Map::new();
+    let type_value = if has_nulls {
+        Value::Array(vec![Value::String(type_name.to_string()), Value::String("null".to_string())])
+    } else {
+        Value::String(type_name.to_string())
+    };
+    property.insert("type".to_string(), type_value);
+
+    if matches!(stats.inferred_type, Some(InferredType::Integer) | Some(InferredType::Number)) {
+        if let Some(min) = stats.min {
+            property.insert("minimum".to_string(), json_number(min));
+        }
+        if let Some(max) = stats.max {
+            property.insert("maximum".to_string(), json_number(max));
+        }
+    }
+
+    if stats.distinct.len() > 1 && stats.distinct.len() <= MAX_ENUM_DISTINCT {
+        let mut values: Vec<&String> = stats.distinct.iter().collect();
+        values.sort();
+        // Coerce each value the way `coerce_scalar` coerces the cell during
+        // validation, so e.g. an integer column lists `1`, not `"1"`.
+        let type_schema = Value::Object(property.clone());
+        let mut enum_values: Vec<Value> = Vec::new();
+        for value in values {
+            let coerced = coerce_scalar(value, Some(&type_schema))
+                .unwrap_or_else(|_| Value::String(value.clone()));
+            if !enum_values.contains(&coerced) {
+                enum_values.push(coerced);
+            }
+        }
+        if has_nulls {
+            enum_values.push(Value::Null);
+        }
+        property.insert("enum".to_string(), Value::Array(enum_values));
+    }
+
+    Value::Object(property)
+}
+
+/// Scans a CSV file (or, if `sample_size` is given, only its first N rows)
+/// and infers a JSON Schema object describing it: each header becomes a
+/// property keyed by the narrowest type that fits every observed value, and
+/// columns with no empty cells are listed as `required`.
+fn infer_schema<R: Read>(input: R, sample_size: Option<usize>) -> Result<Value, String> {
+    let mut csv_reader = csv::ReaderBuilder::new().from_reader(input);
+
+    let headers = csv_reader.headers()
+        .map_err(|e| format!("failed to read csv headers: {:?}", e))?
+        .clone();
+
+    let mut stats: Vec<ColumnStats> = headers.iter().map(|_| ColumnStats::new()).collect();
+
+    for (row_index, result) in csv_reader.records().enumerate() {
+        if let Some(limit) = sample_size {
+            if row_index >= limit {
+                break;
+            }
+        }
+
+        let record = result
+            .map_err(|e| format!("failed to read csv record {}: {:?}", row_index, e))?;
+
+        for (field_index, field) in record.iter().enumerate() {
+            if let Some(column) = stats.get_mut(field_index) {
+                column.observe(field);
+            }
+        }
+    }
+
+    let mut properties = serde_json::Map::new();
+    let mut required = Vec::new();
+
+    for (header, column) in headers.iter().zip(stats.iter()) {
+        properties.insert(header.to_string(), column_schema(column));
+        if column.empty_seen == 0 {
+            required.push(Value::String(header.to_string()));
+        }
+    }
+
+    let mut schema = serde_json::Map::new();
+    schema.insert("type".to_string(), Value::String("object".to_string()));
+    schema.insert("properties".to_string(), Value::Object(properties));
+    schema.insert("required".to_string(), Value::Array(required));
+
+    Ok(Value::Object(schema))
+}
+
+/// A single validation failure against one field of one record.
+struct FieldError {
+    /// The instance path of the offending field, e.g. `/age`.
+    path: String,
+    message: String,
+}
+
+/// All validation failures found for a single (1-based) CSV record.
+struct RecordError {
+    record: usize,
+    errors: Vec<FieldError>,
+}
+
+/// The outcome of validating every record in a CSV file.
+#[derive(Default)]
+struct ValidationReport {
+    success_count: usize,
+    errors: Vec<RecordError>,
+}
+
+/// A custom format checker registered with [`CsvValidator::with_format`].
+/// Checkers must be `Send + Sync` so the map of them can be shared, by
+/// reference, across the worker threads `validate` may spawn.
+type FormatMap = HashMap<String, Box<dyn Fn(&str) -> bool + Send + Sync>>;
+
 /// Validates CSV fields using the rules from a JSON-Schema validator.
 ///
 /// It is important to note that the given JSON Schema must consist of a single
@@ -107,92 +468,530 @@ fn execute(args: &ArgMatches) -> Result<(), String> {
 ///     0,Bobby
 ///     "#;
 ///
-///     validator.validate(Cursor::new(csv));
+///     validator.validate(Cursor::new(csv), 1, ';', false, None);
 /// }
 /// ```
 struct CsvValidator<'a> {
     schema_config: SchemaConfig<'a>,
+    formats: FormatMap,
 }
 
-impl CsvValidator<'_> {
-    pub fn new(schema_config: SchemaConfig) -> CsvValidator {
+impl<'a> CsvValidator<'a> {
+    pub fn new(schema_config: SchemaConfig<'a>) -> CsvValidator<'a> {
+        let mut formats: FormatMap = HashMap::new();
+        formats.insert("currency".to_string(), Box::new(is_valid_currency as fn(&str) -> bool));
+
         CsvValidator {
-            schema_config
+            schema_config,
+            formats,
         }
     }
 
-    pub fn validate<R: Read>(&self, input: R) -> Result<usize, usize> {
+    /// Registers a custom `checker` for the `format` keyword named `name`.
+    /// After the standard JSON Schema validation pass, any property whose
+    /// schema carries `"format": "<name>"` has its raw cell text run through
+    /// `checker`, and a validation error is reported on failure. Registering
+    /// a name that already exists (including the built-in `currency` format)
+    /// replaces it.
+    pub fn with_format<F>(mut self, name: &str, checker: F) -> CsvValidator<'a>
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.formats.insert(name.to_string(), Box::new(checker));
+        self
+    }
+
+    /// Validates every record read from `input` against this validator's
+    /// schema. When `jobs` is `1`, records are checked sequentially on the
+    /// calling thread, exactly as before. When `jobs` is greater than `1`,
+    /// records are instead split across a `rayon` thread pool of that size;
+    /// since `jsonschema_valid::Config` borrows from the schema `Value` it
+    /// was built from and isn't `Sync`, each worker builds its own
+    /// `SchemaConfig` from a cloned copy of the schema (via `map_init`, once
+    /// per rayon job, i.e. per batch of records, rather than once per record) rather than sharing
+    /// `self.schema_config`. `draft` must be the same draft `self.schema_config`
+    /// was built with, so the rebuilt per-worker copies validate against the
+    /// same JSON Schema draft regardless of `jobs`. Per-record results are
+    /// collected back in input order, so error messages stay deterministic
+    /// regardless of `jobs`. `array_delimiter` splits a cell into a JSON
+    /// array for properties whose schema declares `"type": "array"`. When
+    /// `fail_fast` is set, `jobs` is forced to `1` so validation can stop at
+    /// the first failing record instead of racing ahead on other threads.
+    pub fn validate<R: Read>(
+        &self,
+        input: R,
+        jobs: usize,
+        array_delimiter: char,
+        fail_fast: bool,
+        draft: Option<jsonschema_valid::schemas::Draft>,
+    ) -> ValidationReport {
+        let jobs = if fail_fast { 1 } else { jobs };
+
         let mut csv_reader = csv::ReaderBuilder::new()
             .from_reader(input);
 
         let headers = csv_reader.headers().unwrap().clone();
 
-        let mut success_count: usize = 0;
-        let mut error_count: usize = 0;
-        for (record_index, result) in csv_reader.records().enumerate() {
-            let record = match result {
+        // Keep each record's original (pre-filter) row index alongside it, so
+        // a row the csv crate rejects (e.g. wrong field count) doesn't shift
+        // every subsequent record number down by the number of rows dropped.
+        let records: Vec<(usize, csv::StringRecord)> = csv_reader.records()
+            .enumerate()
+            .filter_map(|(record_index, result)| match result {
+                Ok(record) => Some((record_index, record)),
                 Err(e) => {
                     eprintln!("Record error at index {}: {:?}", record_index, e);
-                    continue;
-                },
-                Ok(record) => record,
-            };
-
-            let schema = self.schema_config.get_schema().as_object().unwrap();
-
-            let mut record_map: HashMap<&str, Value> = HashMap::new();
-            for (field_index, (header, field)) in headers.iter().zip(record.iter()).enumerate() {
-
-                // Manually check whether this field has a "string" type in the schema.
-                // If we don't do this, then even though the schema says to treat it
-                // like a string, the JSON parser would read a field like 1234 as a number.
-                let is_string = schema.get("properties")
-                    .and_then(|val| val.as_object())
-                    .and_then(|obj| obj.get(header))
-                    .and_then(|val| val.as_object())
-                    .and_then(|obj| obj.get("type"))
-                    .and_then(|val| val.as_str())
-                    .map(|typ| typ == "string")
-                    .unwrap_or(false);
-
-                // If the schema marks this field as a string, parse it as a string.
-                let maybe_field_value = if is_string {
-                    serde_json::from_str(&format!("\"{}\"", field))
+                    None
+                }
+            })
+            .collect();
+
+        let progress = new_progress_bar(records.len() as u64);
+
+        let all_field_errors: Vec<Vec<FieldError>> = if jobs <= 1 {
+            let mut results = Vec::with_capacity(records.len());
+            for (record_index, record) in records.iter() {
+                let errors = check_record(&self.schema_config, &self.formats, &headers, *record_index, record, array_delimiter, draft.clone());
+                progress.inc(1);
+                let has_errors = !errors.is_empty();
+                results.push(errors);
+                if fail_fast && has_errors {
+                    break;
                 }
-                // Otherwise, parse it like normal JSON
-                else {
-                    serde_json::from_str(field)
-                        .or_else(|_| serde_json::from_str(&format!("\"{}\"", field)))
-                };
-
-                let field_value: Value = match maybe_field_value {
-                    Err(e) => {
-                        eprintln!("Field error at ({}:{}) for field ({}): {:?}", record_index, field_index, field, e);
-                        continue;
-                    },
-                    Ok(value) => value,
-                };
-
-                record_map.insert(header, field_value);
             }
-            let record_value: Value = serde_json::to_value(record_map).unwrap();
+            results
+        } else {
+            let schema_value = self.schema_config.get_schema().clone();
+            let formats = &self.formats;
 
-            let result = self.schema_config.validate(&record_value);
-            match result {
-                Ok(_) => {
-                    success_count += 1;
-                },
-                Err(e) => {
-                    error_count += 1;
-                    eprintln!("Validation error on record {}:", record_index + 1);
-                    for error in e {
-                        eprintln!("{}", error);
-                    }
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(jobs).build()
+                .expect("failed to build rayon thread pool");
+
+            pool.install(|| {
+                records.par_iter()
+                    .map_init(
+                        || SchemaConfig::from_schema(&schema_value, draft.clone())
+                            .expect("schema was already validated once when the CsvValidator was built"),
+                        |schema_config, (record_index, record)| {
+                            let errors = check_record(schema_config, formats, &headers, *record_index, record, array_delimiter, draft.clone());
+                            progress.inc(1);
+                            errors
+                        },
+                    )
+                    .collect()
+            })
+        };
+
+        progress.finish_and_clear();
+
+        let mut report = ValidationReport::default();
+        for ((record_index, _), field_errors) in records.iter().zip(all_field_errors) {
+            if field_errors.is_empty() {
+                report.success_count += 1;
+            } else {
+                report.errors.push(RecordError { record: record_index + 1, errors: field_errors });
+            }
+        }
+
+        report
+    }
+}
+
+/// Reads the declared `"type"` keyword off a property schema, which may be a
+/// single string (`"integer"`) or an array of strings (`["integer", "null"]`).
+fn property_types(property_schema: &Value) -> Vec<&str> {
+    match property_schema.get("type") {
+        Some(Value::String(type_name)) => vec![type_name.as_str()],
+        Some(Value::Array(type_names)) => type_names.iter().filter_map(|v| v.as_str()).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Coerces one CSV cell into a JSON value according to the declared type of
+/// `property_schema`: an empty cell becomes `null` when `"null"` is an
+/// allowed type, and a `"type": "array"` property splits the cell on
+/// `array_delimiter` into a JSON array. Falls through to [`coerce_scalar`]
+/// for every other type.
+fn coerce_field(field: &str, property_schema: Option<&Value>, array_delimiter: char) -> serde_json::Result<Value> {
+    let types = property_schema.map(property_types).unwrap_or_default();
+
+    if field.is_empty() && types.iter().any(|t| *t == "null") {
+        return Ok(Value::Null);
+    }
+
+    if types.iter().any(|t| *t == "array") {
+        let item_schema = property_schema.and_then(|s| s.get("items"));
+        let items = field.split(array_delimiter)
+            .map(|item| coerce_scalar(item, item_schema))
+            .collect::<serde_json::Result<Vec<Value>>>()?;
+        return Ok(Value::Array(items));
+    }
+
+    coerce_scalar(field, property_schema)
+}
+
+/// Coerces one non-array cell into a JSON value: `"boolean"` properties map
+/// `true`/`false`/`1`/`0` (case-insensitively) to a JSON bool, `"integer"`
+/// properties reject values with a fractional part, `"string"` properties
+/// are always quoted, and anything else is parsed like normal JSON, falling
+/// back to a string if that fails. This is what keeps, e.g., a `"string"`
+/// property like a zip code of `12345` from being read as the number `12345`.
+fn coerce_scalar(value: &str, property_schema: Option<&Value>) -> serde_json::Result<Value> {
+    let types = property_schema.map(property_types).unwrap_or_default();
+
+    if types.iter().any(|t| *t == "boolean") {
+        return Ok(match value.to_ascii_lowercase().as_str() {
+            "true" | "1" => Value::Bool(true),
+            "false" | "0" => Value::Bool(false),
+            _ => Value::String(value.to_string()),
+        });
+    }
+
+    if types.iter().any(|t| *t == "integer") {
+        return Ok(match value.parse::<i64>() {
+            Ok(n) if !value.contains('.') => Value::from(n),
+            _ => Value::String(value.to_string()),
+        });
+    }
+
+    if types.iter().any(|t| *t == "string") {
+        return serde_json::from_str(&format!("\"{}\"", value));
+    }
+
+    serde_json::from_str(value)
+        .or_else(|_| serde_json::from_str(&format!("\"{}\"", value)))
+}
+
+/// Checks one CSV record against `schema_config`, parsing its fields and
+/// running any registered format checkers along the way. Returns one
+/// `FieldError` per failure; an empty `Vec` means the record is valid.
+fn check_record(
+    schema_config: &SchemaConfig,
+    formats: &FormatMap,
+    headers: &csv::StringRecord,
+    record_index: usize,
+    record: &csv::StringRecord,
+    array_delimiter: char,
+    draft: Option<jsonschema_valid::schemas::Draft>,
+) -> Vec<FieldError> {
+    let schema = schema_config.get_schema().as_object().unwrap();
+    let properties = schema.get("properties").and_then(|val| val.as_object());
+
+    let mut record_map: HashMap<&str, Value> = HashMap::new();
+    for (field_index, (header, field)) in headers.iter().zip(record.iter()).enumerate() {
+        let property_schema = properties.and_then(|props| props.get(header));
+
+        let field_value = match coerce_field(field, property_schema, array_delimiter) {
+            Err(e) => {
+                eprintln!("Field error at ({}:{}) for field ({}): {:?}", record_index, field_index, field, e);
+                continue;
+            },
+            Ok(value) => value,
+        };
+
+        record_map.insert(header, field_value);
+    }
+    let record_value: Value = serde_json::to_value(&record_map).unwrap();
+
+    let mut field_errors: Vec<FieldError> = Vec::new();
+    if let Err(e) = schema_config.validate(&record_value) {
+        let attributed = attribute_field_errors(properties, headers, &record_map, draft);
+        if attributed.is_empty() {
+            // Couldn't pin any of these to a single property (e.g. a
+            // schema-level keyword like `required` naming a field that's
+            // missing from the record entirely) — report against the
+            // record root rather than guessing a path from the message text.
+            for error in e {
+                field_errors.push(FieldError { path: "/".to_string(), message: error.to_string() });
+            }
+        } else {
+            field_errors.extend(attributed);
+        }
+    }
+    field_errors.extend(check_formats(schema, formats, headers, record));
+
+    field_errors
+}
+
+/// Re-validates each property individually against its own schema node to
+/// attribute a field-level error to the header it belongs to. Only called
+/// once a record is already known to have at least one schema violation:
+/// `jsonschema_valid`'s validation errors only expose a rendered message, not
+/// a structured instance path, so the only reliable way to know which
+/// property actually failed is to validate that property in isolation.
+/// Walks `headers` in declared order (not `record_map`'s `HashMap` order) so
+/// a record with multiple invalid fields reports them deterministically.
+fn attribute_field_errors(
+    properties: Option<&serde_json::Map<String, Value>>,
+    headers: &csv::StringRecord,
+    record_map: &HashMap<&str, Value>,
+    draft: Option<jsonschema_valid::schemas::Draft>,
+) -> Vec<FieldError> {
+    let properties = match properties {
+        Some(properties) => properties,
+        None => return Vec::new(),
+    };
+
+    let mut errors = Vec::new();
+    for header in headers.iter() {
+        let value = match record_map.get(header) {
+            Some(value) => value,
+            None => continue,
+        };
+        let property_schema = match properties.get(header) {
+            Some(property_schema) => property_schema,
+            None => continue,
+        };
+
+        let mut field_properties = serde_json::Map::new();
+        field_properties.insert(header.to_string(), property_schema.clone());
+        let mut field_schema = serde_json::Map::new();
+        field_schema.insert("type".to_string(), Value::String("object".to_string()));
+        field_schema.insert("properties".to_string(), Value::Object(field_properties));
+        let field_schema = Value::Object(field_schema);
+
+        let field_config = match SchemaConfig::from_schema(&field_schema, draft.clone()) {
+            Ok(field_config) => field_config,
+            Err(_) => continue,
+        };
+
+        let mut field_value = serde_json::Map::new();
+        field_value.insert(header.to_string(), value.clone());
+
+        if let Err(e) = field_config.validate(&Value::Object(field_value)) {
+            for error in e {
+                errors.push(FieldError { path: format!("/{}", header), message: error.to_string() });
+            }
+        }
+    }
+    errors
+}
+
+/// Runs any registered custom format checkers against a record's raw field
+/// text, returning one `FieldError` per failing field.
+fn check_formats(
+    schema: &serde_json::Map<String, Value>,
+    formats: &FormatMap,
+    headers: &csv::StringRecord,
+    record: &csv::StringRecord,
+) -> Vec<FieldError> {
+    let properties = match schema.get("properties").and_then(|val| val.as_object()) {
+        Some(properties) => properties,
+        None => return Vec::new(),
+    };
+
+    let mut errors = Vec::new();
+    for (header, raw_value) in headers.iter().zip(record.iter()) {
+        let format_name = properties.get(header)
+            .and_then(|val| val.as_object())
+            .and_then(|obj| obj.get("format"))
+            .and_then(|val| val.as_str());
+
+        if let Some(format_name) = format_name {
+            if let Some(checker) = formats.get(format_name) {
+                if !checker(raw_value) {
+                    errors.push(FieldError {
+                        path: format!("/{}", header),
+                        message: format!("\"{}\" is not a valid \"{}\"", raw_value, format_name),
+                    });
                 }
             }
         }
+    }
+    errors
+}
+
+/// Builds a progress bar that advances once per validated record. When
+/// stderr isn't a terminal (e.g. output is piped or running in CI), returns
+/// a hidden bar so `--jobs`/`--format json` scripting stays clean.
+fn new_progress_bar(len: u64) -> ProgressBar {
+    if atty::is(atty::Stream::Stderr) {
+        let bar = ProgressBar::new(len);
+        bar.set_style(ProgressStyle::default_bar()
+            .template("{bar:40.cyan/blue} {pos}/{len} records ({eta} left)")
+            .unwrap());
+        bar
+    } else {
+        ProgressBar::hidden()
+    }
+}
+
+
+/// The built-in `currency` format: accepts `0` or a positive integer with no
+/// leading zeros, optionally followed by a decimal point and exactly two
+/// digits. Equivalent to the regex `^(0|[1-9]\d*)(\.\d{2})?$`.
+fn is_valid_currency(value: &str) -> bool {
+    let (integer_part, fraction_part) = match value.split_once('.') {
+        Some((integer_part, fraction_part)) => (integer_part, Some(fraction_part)),
+        None => (value, None),
+    };
+
+    let integer_valid = integer_part == "0"
+        || (!integer_part.is_empty()
+            && !integer_part.starts_with('0')
+            && integer_part.chars().all(|c| c.is_ascii_digit()));
+
+    let fraction_valid = match fraction_part {
+        Some(fraction_part) => fraction_part.len() == 2 && fraction_part.chars().all(|c| c.is_ascii_digit()),
+        None => true,
+    };
+
+    integer_valid && fraction_valid
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn widen_same_type_stays_unchanged() {
+        assert_eq!(widen(InferredType::Integer, InferredType::Integer), InferredType::Integer);
+        assert_eq!(widen(InferredType::String, InferredType::String), InferredType::String);
+    }
+
+    #[test]
+    fn widen_integer_and_number_becomes_number() {
+        assert_eq!(widen(InferredType::Integer, InferredType::Number), InferredType::Number);
+        assert_eq!(widen(InferredType::Number, InferredType::Integer), InferredType::Number);
+    }
+
+    #[test]
+    fn widen_anything_else_falls_back_to_string() {
+        assert_eq!(widen(InferredType::Integer, InferredType::Boolean), InferredType::String);
+        assert_eq!(widen(InferredType::Boolean, InferredType::String), InferredType::String);
+    }
+
+    #[test]
+    fn column_schema_infers_boolean_for_true_false_values() {
+        let mut stats = ColumnStats::new();
+        stats.observe("true");
+        stats.observe("false");
+        stats.observe("TRUE");
+
+        let schema = column_schema(&stats);
+        assert_eq!(schema["type"], Value::String("boolean".to_string()));
+    }
+
+    #[test]
+    fn column_schema_infers_integer_when_every_value_parses() {
+        let mut stats = ColumnStats::new();
+        stats.observe("1");
+        stats.observe("42");
+
+        let schema = column_schema(&stats);
+        assert_eq!(schema["type"], Value::String("integer".to_string()));
+        assert_eq!(schema["minimum"], json_number(1.0));
+        assert_eq!(schema["maximum"], json_number(42.0));
+    }
+
+    #[test]
+    fn column_schema_widens_integer_and_number_to_number() {
+        let mut stats = ColumnStats::new();
+        stats.observe("1");
+        stats.observe("2.5");
+
+        let schema = column_schema(&stats);
+        assert_eq!(schema["type"], Value::String("number".to_string()));
+    }
+
+    #[test]
+    fn column_schema_marks_type_and_enum_nullable_when_column_has_empty_cells() {
+        let mut stats = ColumnStats::new();
+        stats.observe("red");
+        stats.observe("blue");
+        stats.observe("");
+
+        let schema = column_schema(&stats);
+        assert_eq!(
+            schema["type"],
+            Value::Array(vec![Value::String("string".to_string()), Value::String("null".to_string())])
+        );
+        assert!(schema["enum"].as_array().unwrap().contains(&Value::Null));
+    }
+
+    #[test]
+    fn column_schema_defaults_to_string_when_no_values_observed() {
+        let stats = ColumnStats::new();
+        let schema = column_schema(&stats);
+        assert_eq!(schema["type"], Value::String("string".to_string()));
+    }
+
+    #[test]
+    fn currency_accepts_zero_and_plain_integers() {
+        assert!(is_valid_currency("0"));
+        assert!(is_valid_currency("7"));
+        assert!(is_valid_currency("1234"));
+    }
+
+    #[test]
+    fn currency_accepts_exactly_two_fraction_digits() {
+        assert!(is_valid_currency("0.00"));
+        assert!(is_valid_currency("19.99"));
+    }
+
+    #[test]
+    fn currency_rejects_leading_zeros() {
+        assert!(!is_valid_currency("007.5"));
+        assert!(!is_valid_currency("01"));
+    }
+
+    #[test]
+    fn currency_rejects_wrong_fraction_length() {
+        assert!(!is_valid_currency("1.234"));
+        assert!(!is_valid_currency("1.2"));
+    }
+
+    #[test]
+    fn currency_rejects_negatives_and_non_numeric() {
+        assert!(!is_valid_currency("-5"));
+        assert!(!is_valid_currency("abc"));
+        assert!(!is_valid_currency(""));
+    }
+
+    #[test]
+    fn coerce_field_maps_empty_cell_to_null_when_nullable() {
+        let schema: Value = serde_json::from_str(r#"{"type": ["integer", "null"]}"#).unwrap();
+        assert_eq!(coerce_field("", Some(&schema), ';').unwrap(), Value::Null);
+    }
+
+    #[test]
+    fn coerce_field_keeps_empty_cell_as_string_when_not_nullable() {
+        let schema: Value = serde_json::from_str(r#"{"type": "string"}"#).unwrap();
+        assert_eq!(coerce_field("", Some(&schema), ';').unwrap(), Value::String("".to_string()));
+    }
+
+    #[test]
+    fn coerce_field_splits_array_type_on_the_delimiter() {
+        let schema: Value = serde_json::from_str(r#"{"type": "array", "items": {"type": "integer"}}"#).unwrap();
+        assert_eq!(
+            coerce_field("1;2;3", Some(&schema), ';').unwrap(),
+            Value::Array(vec![Value::from(1), Value::from(2), Value::from(3)])
+        );
+    }
+
+    #[test]
+    fn coerce_scalar_maps_boolean_keywords_case_insensitively() {
+        let schema: Value = serde_json::from_str(r#"{"type": "boolean"}"#).unwrap();
+        assert_eq!(coerce_scalar("TRUE", Some(&schema)).unwrap(), Value::Bool(true));
+        assert_eq!(coerce_scalar("0", Some(&schema)).unwrap(), Value::Bool(false));
+    }
+
+    #[test]
+    fn coerce_scalar_rejects_fractional_integers() {
+        let schema: Value = serde_json::from_str(r#"{"type": "integer"}"#).unwrap();
+        assert_eq!(coerce_scalar("5", Some(&schema)).unwrap(), Value::from(5));
+        assert_eq!(coerce_scalar("5.5", Some(&schema)).unwrap(), Value::String("5.5".to_string()));
+    }
+
+    #[test]
+    fn coerce_scalar_keeps_string_properties_quoted() {
+        let schema: Value = serde_json::from_str(r#"{"type": "string"}"#).unwrap();
+        assert_eq!(coerce_scalar("01234", Some(&schema)).unwrap(), Value::String("01234".to_string()));
+    }
 
-        if error_count == 0 { Ok(success_count) }
-        else { Err(error_count) }
+    #[test]
+    fn coerce_scalar_falls_back_to_string_without_a_schema() {
+        assert_eq!(coerce_scalar("hello", None).unwrap(), Value::String("hello".to_string()));
+        assert_eq!(coerce_scalar("42", None).unwrap(), Value::from(42));
     }
 }